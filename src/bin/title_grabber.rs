@@ -1,5 +1,7 @@
+use std::io::{self, Write};
 use std::path::Path;
 use std::process;
+use std::time::Duration;
 
 use clap::{App, Arg};
 
@@ -13,15 +15,36 @@ lazy_static! {
 }
 
 use title_grabber_rs::{
-    TitleGrabber, CONN_TO, DEF_OUT_PATH, MAX_REDIRECTS, MAX_RETRIES, NUM_CPUS, READ_TO,
+    Encoding, OutputFormat, TitleGrabber, BACKOFF_BASE_MS, CONN_TO, DEF_OUT_PATH, MAX_BACKOFF_MS,
+    MAX_BODY_BYTES, MAX_PER_HOST, MAX_REDIRECTS, MAX_RETRIES, NUM_CPUS, READ_TO,
 };
 
+/// Parses `--encodings`' comma-separated list into the codecs
+/// `with_accepted_encodings` understands, skipping any name it doesn't
+/// recognize rather than failing the whole run over a typo.
+fn parse_encodings(value: &str) -> Vec<Encoding> {
+    value
+        .split(',')
+        .filter_map(|name| match name.trim().to_lowercase().as_str() {
+            "gzip" => Some(Encoding::Gzip),
+            "brotli" | "br" => Some(Encoding::Brotli),
+            "deflate" => Some(Encoding::Deflate),
+            "zstd" => Some(Encoding::Zstd),
+            _ => None,
+        })
+        .collect()
+}
+
 fn main() {
     let def_conn_to = CONN_TO.to_string();
     let def_read_to = READ_TO.to_string();
     let def_max_redirects = MAX_REDIRECTS.to_string();
     let def_max_retries = MAX_RETRIES.to_string();
+    let def_backoff_base = BACKOFF_BASE_MS.to_string();
+    let def_max_backoff = MAX_BACKOFF_MS.to_string();
     let def_max_threads = NUM_CPUS.to_string();
+    let def_max_body_bytes = MAX_BODY_BYTES.to_string();
+    let def_max_per_host = MAX_PER_HOST.to_string();
 
     let matches = App::new("title_grabber")
         .version("0.1.0")
@@ -47,7 +70,6 @@ fn main() {
                 .takes_value(true)
                 .env("CONNECT_TIMEOUT")
                 .default_value(&def_conn_to)
-                // .default_value(str::from_utf8(&[CONN_TO]).unwrap())
                 .help(&format!("HTTP connect timeout. Defaults to the value of the CONNECT_TIMEOUT env var or {}", CONN_TO)),
         )
         .arg(
@@ -74,13 +96,115 @@ fn main() {
                 .default_value(&def_max_retries)
                 .help(&format!("Max. # of times to retry failed HTTP reqs. Defaults to the value of the MAX_RETRIES env var or {}", MAX_RETRIES)),
         )
+        .arg(
+            Arg::with_name("backoff-base")
+                .long("backoff-base")
+                .takes_value(true)
+                .env("BACKOFF_BASE_MS")
+                .default_value(&def_backoff_base)
+                .help(&format!("Base delay in ms for exponential backoff between retries (doubles each attempt, jittered). Defaults to the value of the BACKOFF_BASE_MS env var or {}", BACKOFF_BASE_MS)),
+        )
+        .arg(
+            Arg::with_name("max-backoff")
+                .long("max-backoff")
+                .takes_value(true)
+                .env("MAX_BACKOFF_MS")
+                .default_value(&def_max_backoff)
+                .help(&format!("Upper bound in ms on the exponential backoff delay between retries, before jitter. Defaults to the value of the MAX_BACKOFF_MS env var or {}", MAX_BACKOFF_MS)),
+        )
         .arg(
             Arg::with_name("max-threads")
                 .short("t")
                 .takes_value(true)
                 .env("MAX_THREADS")
                 .default_value(&def_max_threads)
-                .help(&format!("Max. # of threads to use. Defaults to the value of the MAX_THREADS env var or the # of logical processors in the system ({})", def_max_threads)),
+                .help(&format!("Max. # of concurrent requests to have in flight. Defaults to the value of the MAX_THREADS env var or the # of logical processors in the system ({})", def_max_threads)),
+        )
+        .arg(
+            Arg::with_name("max-body-bytes")
+                .long("max-body-bytes")
+                .takes_value(true)
+                .env("MAX_BODY_BYTES")
+                .default_value(&def_max_body_bytes)
+                .help(&format!("Max. # of response body bytes to read before giving up on a URL. Defaults to the value of the MAX_BODY_BYTES env var or {}", MAX_BODY_BYTES)),
+        )
+        .arg(
+            Arg::with_name("encodings")
+                .long("encodings")
+                .takes_value(true)
+                .env("ENCODINGS")
+                .help("Comma-separated content-codings to accept: gzip, deflate, brotli, zstd. Defaults to gzip,deflate; brotli/zstd are opt-in"),
+        )
+        .arg(
+            Arg::with_name("cookies")
+                .long("cookies")
+                .env("COOKIES")
+                .help("Keep a cookie jar across requests, so a consent/session cookie set on the first hit to a host is replayed on later ones"),
+        )
+        .arg(
+            Arg::with_name("cookie-file")
+                .long("cookie-file")
+                .takes_value(true)
+                .env("COOKIE_FILE")
+                .help("Seed the cookie jar from a Netscape- or JSON-format cookie file before the first request. Implies --cookies"),
+        )
+        .arg(
+            Arg::with_name("no-cache")
+                .long("no-cache")
+                .env("NO_CACHE")
+                .help("Skip reusing any previously scraped rows and fetch every URL fresh"),
+        )
+        .arg(
+            Arg::with_name("cache-path")
+                .long("cache-path")
+                .takes_value(true)
+                .env("CACHE_PATH")
+                .help("Read previously scraped rows from this file instead of the output file"),
+        )
+        .arg(
+            Arg::with_name("max-cache-age")
+                .long("max-cache-age")
+                .takes_value(true)
+                .env("MAX_CACHE_AGE")
+                .help("Max. age in seconds of a cached row before it's revalidated instead of reused outright. Unset means always revalidate"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .short("w")
+                .long("watch")
+                .env("WATCH")
+                .help("Keep running and re-scrape whenever an input file changes"),
+        )
+        .arg(
+            Arg::with_name("max-per-host")
+                .long("max-per-host")
+                .takes_value(true)
+                .env("MAX_PER_HOST")
+                .default_value(&def_max_per_host)
+                .help(&format!("Max. # of concurrent requests to have in flight to any single host. Defaults to the value of the MAX_PER_HOST env var or {}", MAX_PER_HOST)),
+        )
+        .arg(
+            Arg::with_name("min-host-delay")
+                .long("min-host-delay")
+                .takes_value(true)
+                .env("MIN_HOST_DELAY")
+                .help("Min. # of seconds to wait between successive requests to the same host. Unset means no minimum delay"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .env("FORMAT")
+                .possible_values(&["csv", "ndjson", "json"])
+                .default_value("csv")
+                .help("Output format: csv, ndjson (1 JSON record per line) or json (a single JSON array). Defaults to csv"),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .short("p")
+                .long("progress")
+                .env("PROGRESS")
+                .help("Print a live \"N/M processed, F failed\" line to STDERR as results come in"),
         )
         .arg(
             Arg::with_name("files")
@@ -91,21 +215,16 @@ fn main() {
         )
         .get_matches();
 
-    println!("{:?}", matches);
-
     let out_path = matches.value_of("output").unwrap_or(DEF_OUT_PATH);
 
     if let Some(files) = matches.values_of("files") {
         let files: Vec<&Path> = files.map(|f| f.as_ref()).collect();
 
-        // let mut instance = TitleGrabber::new(files, out_path.as_ref());
-        let mut instance = TitleGrabber::new(files);
+        let debug = matches.value_of("debug").map_or(false, |debug| {
+            TRUE_VALS.iter().any(|&true_val| debug == true_val)
+        });
 
-        if let Some(debug) = matches.value_of("debug") {
-            if TRUE_VALS.iter().any(|&true_val| debug == true_val) {
-                instance.enable_debug_mode();
-            }
-        }
+        let mut instance = TitleGrabber::new(files, out_path.as_ref(), debug);
 
         let conn_to = matches
             .value_of("connect-timeout")
@@ -135,15 +254,108 @@ fn main() {
             .unwrap_or(MAX_RETRIES);
         instance.with_max_retries(max_retries);
 
+        let backoff_base: u64 = matches
+            .value_of("backoff-base")
+            .unwrap()
+            .parse()
+            .unwrap_or(BACKOFF_BASE_MS);
+        instance.with_backoff_base(Duration::from_millis(backoff_base));
+
+        let max_backoff: u64 = matches
+            .value_of("max-backoff")
+            .unwrap()
+            .parse()
+            .unwrap_or(MAX_BACKOFF_MS);
+        instance.with_max_backoff(Duration::from_millis(max_backoff));
+
         let max_threads: usize = matches
             .value_of("max-threads")
             .unwrap()
             .parse()
-            .unwrap_or(num_cpus::get());
+            .unwrap_or(*NUM_CPUS);
         instance.with_max_threads(max_threads);
 
-        if let Some(err) = instance.write_csv_to(out_path.as_ref()).err() {
-            eprintln!("Error: {}", err.description());
+        let max_body_bytes: usize = matches
+            .value_of("max-body-bytes")
+            .unwrap()
+            .parse()
+            .unwrap_or(MAX_BODY_BYTES);
+        instance.with_max_body_bytes(max_body_bytes);
+
+        if let Some(encodings) = matches.value_of("encodings") {
+            instance.with_accepted_encodings(parse_encodings(encodings));
+        }
+
+        if matches.is_present("cookies") {
+            instance.with_cookies(true);
+        }
+
+        if let Some(cookie_file) = matches.value_of("cookie-file") {
+            instance.with_cookie_file(cookie_file.as_ref());
+        }
+
+        let max_per_host: usize = matches
+            .value_of("max-per-host")
+            .unwrap()
+            .parse()
+            .unwrap_or(MAX_PER_HOST);
+        instance.with_max_per_host(max_per_host);
+
+        if let Some(min_host_delay) = matches.value_of("min-host-delay") {
+            if let Ok(secs) = min_host_delay.parse() {
+                instance.with_host_delay(Duration::from_secs(secs));
+            }
+        }
+
+        if matches.is_present("no-cache") {
+            instance.with_no_cache(true);
+        }
+
+        if let Some(cache_path) = matches.value_of("cache-path") {
+            instance.with_cache_path(cache_path.as_ref());
+        }
+
+        if let Some(max_cache_age) = matches.value_of("max-cache-age") {
+            if let Ok(secs) = max_cache_age.parse() {
+                instance.with_max_cache_age(Duration::from_secs(secs));
+            }
+        }
+
+        if matches.is_present("watch") {
+            instance.enable_watch_mode(true);
+        }
+
+        let format = match matches.value_of("format").unwrap() {
+            "ndjson" => OutputFormat::Ndjson,
+            "json" => OutputFormat::Json,
+            _ => OutputFormat::Csv,
+        };
+        instance.with_format(format);
+
+        let show_progress = matches.is_present("progress");
+        let result = instance.run(|row, processed, total, failures| {
+            if !show_progress {
+                return;
+            }
+
+            let status = match row {
+                Some(row) => row.url(),
+                None => "FAILED",
+            };
+            eprint!(
+                "\r{}/{} processed, {} failed - {}\x1b[K",
+                processed, total, failures, status
+            );
+            let _ = io::stderr().flush();
+        });
+
+        if show_progress {
+            eprintln!();
+        }
+
+        if let Some(err) = result.err() {
+            eprintln!("Error: {}", err);
+            process::exit(1);
         }
     } else {
         eprintln!("At least 1 input file is required!");
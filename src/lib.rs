@@ -1,37 +1,55 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{prelude::*, BufReader};
-use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{mpsc, Arc};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use csv;
 use flexi_logger::{detailed_format, Duplicate, Logger};
+use futures::stream::{self, StreamExt};
 use itertools;
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate log;
 use num_cpus;
+use rand::Rng;
 use regex::Regex;
 use reqwest::{self, Url};
 #[macro_use]
 extern crate serde_derive;
-use scoped_threadpool::Pool;
 use scraper::{Html, Selector};
+use serde_json;
+use tokio::runtime::Runtime;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 pub const DEF_OUT_PATH: &str = "output.csv";
 pub const CONN_TO: u64 = 30;
 pub const READ_TO: u64 = 30;
 pub const MAX_REDIRECTS: usize = 5;
 pub const MAX_RETRIES: u64 = 3;
+pub const BACKOFF_BASE_MS: u64 = 200;
+pub const MAX_BACKOFF_MS: u64 = 30_000;
+pub const MAX_BODY_BYTES: usize = 20 * 1024 * 1024;
+pub const MAX_PER_HOST: usize = 4;
+const WATCH_DEBOUNCE_MS: u64 = 300;
+const SNIFF_BYTES: usize = 512;
+const HTML_CONTENT_TYPES: [&str; 2] = ["text/html", "application/xhtml+xml"];
+const GENERIC_CONTENT_TYPES: [&str; 2] = ["application/octet-stream", "binary/octet-stream"];
 const END_URL_HEAD: &str = "end_url";
 const PAGE_TIT_HEAD: &str = "page_title";
 const ART_TIT_HEAD: &str = "article_title";
+const ETAG_HEAD: &str = "etag";
+const LAST_MOD_HEAD: &str = "last_modified";
+const CONTENT_TYPE_HEAD: &str = "content_type";
+const CACHED_AT_HEAD: &str = "cached_at";
+const IF_NONE_MATCH_HEADER: &str = "If-None-Match";
+const IF_MODIFIED_SINCE_HEADER: &str = "If-Modified-Since";
 const TWEET_PERMA_LINK_SEL: &str = ".tweet.permalink-tweet";
 const TWEET_TXT_SELS: [&str; 2] = [".tweet-text", "QuoteTweet"];
 const TWITTER_HOST: &str = "twitter.com";
@@ -55,12 +73,222 @@ fn fix_whitespace(html: String) -> String {
         .into_owned()
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct CsvRow {
+fn normalize_content_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn looks_like_html(body: &[u8]) -> bool {
+    let sample = String::from_utf8_lossy(body).to_ascii_lowercase();
+    sample.contains("<html") || sample.contains("<!doctype html")
+}
+
+fn sniff_content_type(body: &[u8]) -> String {
+    if looks_like_html(body) {
+        "text/html".to_owned()
+    } else if body.starts_with(b"%PDF") {
+        "application/pdf".to_owned()
+    } else if body.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png".to_owned()
+    } else if body.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "image/jpeg".to_owned()
+    } else if body.starts_with(b"GIF87a") || body.starts_with(b"GIF89a") {
+        "image/gif".to_owned()
+    } else if body.starts_with(b"PK\x03\x04") {
+        "application/zip".to_owned()
+    } else {
+        "application/octet-stream".to_owned()
+    }
+}
+
+enum SniffedBody {
+    Html(String),
+    Other(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Brotli,
+    Deflate,
+    Zstd,
+}
+
+/// Output serialization selected via `with_format`/`write_to`. `Csv` keeps the
+/// existing flattened, comma-joined `end_url` redirect chain; `Ndjson` and
+/// `Json` serialize it as a real array instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Ndjson,
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+struct CookieFileEntry {
+    url: String,
+    name: String,
+    value: String,
+}
+
+/// A resolved record for a single URL, as written to the output file and
+/// passed to `run`/`watch`'s `on_row` callback and `stream`'s `StreamItem`
+/// (`None` there means the URL failed). Fields are private; accessors below
+/// give callers a read-only view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvRow {
     url: String,
     end_url: String,
     page_title: Option<String>,
     article_title: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_type: Option<String>,
+    cached_at: Option<i64>,
+}
+
+/// A `CsvRow` as seen by `OutputFormat::Ndjson`/`OutputFormat::Json`, with the
+/// comma-joined `end_url` redirect chain expanded back into a real array
+/// instead of being flattened into a single lossy string.
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    url: &'a str,
+    end_url: Vec<&'a str>,
+    page_title: &'a Option<String>,
+    article_title: &'a Option<String>,
+    etag: &'a Option<String>,
+    last_modified: &'a Option<String>,
+    content_type: &'a Option<String>,
+    cached_at: Option<i64>,
+}
+
+impl CsvRow {
+    /// The URL as given in the input file.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The final URL after following any redirects (comma-joined if more
+    /// than one, e.g. quoted tweet URLs found in the page).
+    pub fn end_url(&self) -> &str {
+        &self.end_url
+    }
+
+    pub fn page_title(&self) -> Option<&str> {
+        self.page_title.as_deref()
+    }
+
+    pub fn article_title(&self) -> Option<&str> {
+        self.article_title.as_deref()
+    }
+
+    fn as_json_record(&self) -> JsonRecord {
+        JsonRecord {
+            url: &self.url,
+            end_url: self.end_url.split(CSV_FIELD_SEP).collect(),
+            page_title: &self.page_title,
+            article_title: &self.article_title,
+            etag: &self.etag,
+            last_modified: &self.last_modified,
+            content_type: &self.content_type,
+            cached_at: self.cached_at,
+        }
+    }
+}
+
+/// One message pulled off the receiver `stream` returns: either a resolved
+/// (or failed, `row: None`) record plus running totals, or - on the final
+/// message only - a fatal `error` that ended the run early (e.g. the output
+/// file couldn't be created).
+pub struct StreamItem {
+    pub row: Option<CsvRow>,
+    pub processed: usize,
+    pub total: usize,
+    pub failures: usize,
+    pub error: Option<String>,
+}
+
+fn now_unix_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn host_of(url: &str) -> Option<String> {
+    Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_owned))
+}
+
+fn row_from_cache(url: String, mut row: HashMap<&'static str, Option<String>>) -> CsvRow {
+    CsvRow {
+        url,
+        end_url: row.remove(END_URL_HEAD).unwrap().unwrap(),
+        page_title: row.remove(PAGE_TIT_HEAD).unwrap(),
+        article_title: row.remove(ART_TIT_HEAD).unwrap(),
+        etag: row.remove(ETAG_HEAD).unwrap(),
+        last_modified: row.remove(LAST_MOD_HEAD).unwrap(),
+        content_type: row.remove(CONTENT_TYPE_HEAD).unwrap(),
+        cached_at: row
+            .remove(CACHED_AT_HEAD)
+            .unwrap()
+            .and_then(|v| v.parse().ok()),
+    }
+}
+
+/// Keeps per-host concurrency caps and minimum request spacing so a batch of
+/// URLs sharing a host doesn't get fired at it all at once. Shared across
+/// scrape tasks behind an `Arc`.
+struct HostThrottle {
+    max_per_host: usize,
+    min_delay: Option<Duration>,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+    last_request_at: Mutex<HashMap<String, SystemTime>>,
+}
+
+impl HostThrottle {
+    fn new(max_per_host: usize, min_delay: Option<Duration>) -> Self {
+        Self {
+            max_per_host,
+            min_delay,
+            semaphores: Mutex::new(HashMap::new()),
+            last_request_at: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn acquire(&self, host: &str) -> OwnedSemaphorePermit {
+        let semaphore = self
+            .semaphores
+            .lock()
+            .unwrap()
+            .entry(host.to_owned())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+            .clone();
+
+        let permit = semaphore.acquire_owned().await.unwrap();
+
+        if let Some(min_delay) = self.min_delay {
+            let wait_until = {
+                let mut last_request_at = self.last_request_at.lock().unwrap();
+                let now = SystemTime::now();
+                let earliest = last_request_at
+                    .get(host)
+                    .map_or(now, |&last| last + min_delay);
+                let wait_until = earliest.max(now);
+                last_request_at.insert(host.to_owned(), wait_until);
+                wait_until
+            };
+
+            if let Ok(delay) = wait_until.duration_since(SystemTime::now()) {
+                tokio::time::sleep(delay).await;
+            }
+        }
+
+        permit
+    }
 }
 
 pub struct TitleGrabber<'a> {
@@ -70,7 +298,78 @@ pub struct TitleGrabber<'a> {
     read_timeout: u64,
     max_redirects: usize,
     max_retries: u64,
+    backoff_base: Duration,
+    max_backoff: Duration,
+    max_threads: usize,
+    accepted_encodings: Vec<Encoding>,
+    cookies_enabled: bool,
+    cookie_file: Option<&'a Path>,
+    max_body_bytes: usize,
+    no_cache: bool,
+    cache_path: Option<&'a Path>,
+    max_cache_age: Option<Duration>,
+    watch_mode: bool,
+    max_per_host: usize,
+    host_delay: Option<Duration>,
+    format: OutputFormat,
+}
+
+/// Fully-owned twin of `TitleGrabber` (`PathBuf` in place of every `&'a
+/// Path` field), used to move a `TitleGrabber`'s configuration across a
+/// thread boundary. `borrow` then hands back a `TitleGrabber` that borrows
+/// from this snapshot's own paths, built only after the snapshot has
+/// already reached its final location - so nothing is ever left borrowing
+/// data that's about to be relocated.
+struct ConfigSnapshot {
+    files: Vec<PathBuf>,
+    output_path: PathBuf,
+    connect_timeout: u64,
+    read_timeout: u64,
+    max_redirects: usize,
+    max_retries: u64,
+    backoff_base: Duration,
+    max_backoff: Duration,
     max_threads: usize,
+    accepted_encodings: Vec<Encoding>,
+    cookies_enabled: bool,
+    cookie_file: Option<PathBuf>,
+    max_body_bytes: usize,
+    no_cache: bool,
+    cache_path: Option<PathBuf>,
+    max_cache_age: Option<Duration>,
+    watch_mode: bool,
+    max_per_host: usize,
+    host_delay: Option<Duration>,
+    format: OutputFormat,
+}
+
+impl ConfigSnapshot {
+    /// Built as a direct struct literal (not `TitleGrabber::new`) so this
+    /// doesn't re-initialize the process-wide logger.
+    fn borrow(&self) -> TitleGrabber {
+        TitleGrabber {
+            files: self.files.iter().map(PathBuf::as_path).collect(),
+            output_path: self.output_path.as_path(),
+            connect_timeout: self.connect_timeout,
+            read_timeout: self.read_timeout,
+            max_redirects: self.max_redirects,
+            max_retries: self.max_retries,
+            backoff_base: self.backoff_base,
+            max_backoff: self.max_backoff,
+            max_threads: self.max_threads,
+            accepted_encodings: self.accepted_encodings.clone(),
+            cookies_enabled: self.cookies_enabled,
+            cookie_file: self.cookie_file.as_deref(),
+            max_body_bytes: self.max_body_bytes,
+            no_cache: self.no_cache,
+            cache_path: self.cache_path.as_deref(),
+            max_cache_age: self.max_cache_age,
+            watch_mode: self.watch_mode,
+            max_per_host: self.max_per_host,
+            host_delay: self.host_delay,
+            format: self.format,
+        }
+    }
 }
 
 impl<'a> TitleGrabber<'a> {
@@ -100,7 +399,20 @@ impl<'a> TitleGrabber<'a> {
             read_timeout: READ_TO,
             max_redirects: MAX_REDIRECTS,
             max_retries: MAX_RETRIES,
+            backoff_base: Duration::from_millis(BACKOFF_BASE_MS),
+            max_backoff: Duration::from_millis(MAX_BACKOFF_MS),
             max_threads: *NUM_CPUS,
+            accepted_encodings: vec![Encoding::Gzip, Encoding::Deflate],
+            cookies_enabled: false,
+            cookie_file: None,
+            max_body_bytes: MAX_BODY_BYTES,
+            no_cache: false,
+            cache_path: None,
+            max_cache_age: None,
+            watch_mode: false,
+            max_per_host: MAX_PER_HOST,
+            host_delay: None,
+            format: OutputFormat::Csv,
         }
     }
 
@@ -124,16 +436,122 @@ impl<'a> TitleGrabber<'a> {
         self
     }
 
+    /// Base delay for the exponential backoff `get` waits between retries
+    /// (`backoff_base * 2^attempt`, capped by `with_max_backoff` and padded
+    /// with jitter). Defaults to `BACKOFF_BASE_MS`.
+    pub fn with_backoff_base(&mut self, base: Duration) -> &mut Self {
+        self.backoff_base = base;
+        self
+    }
+
+    /// Upper bound on the exponential backoff delay between retries, before
+    /// jitter is applied. Defaults to `MAX_BACKOFF_MS`.
+    pub fn with_max_backoff(&mut self, max_backoff: Duration) -> &mut Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
     pub fn with_max_threads(&mut self, threads: usize) -> &mut Self {
         self.max_threads = threads;
         self
     }
 
+    /// Restricts which content-codings `build_http_client` will offer via
+    /// `Accept-Encoding` and transparently decode. Brotli and Zstd are opt-in
+    /// here since they pull in extra decoder deps on top of reqwest's default
+    /// gzip/deflate support.
+    pub fn with_accepted_encodings(&mut self, encodings: Vec<Encoding>) -> &mut Self {
+        self.accepted_encodings = encodings;
+        self
+    }
+
+    /// Keeps a cookie jar across requests made from the same `http_client`, so
+    /// a consent/session cookie set on the first hit to a host is replayed on
+    /// later requests to it (including the tweet-following GETs in
+    /// `scrape_url`).
+    pub fn with_cookies(&mut self, enabled: bool) -> &mut Self {
+        self.cookies_enabled = enabled;
+        self
+    }
+
+    /// Seeds the cookie jar from a Netscape-format or JSON cookie file before
+    /// the first request, for pre-authenticating against login-gated hosts.
+    /// Implies `with_cookies(true)`.
+    pub fn with_cookie_file(&mut self, path: &'a Path) -> &mut Self {
+        self.cookies_enabled = true;
+        self.cookie_file = Some(path);
+        self
+    }
+
+    /// Caps how many bytes of a response body are read before it's discarded,
+    /// so a stray link to a multi-gigabyte file can't stall a worker or
+    /// exhaust memory.
+    pub fn with_max_body_bytes(&mut self, max_bytes: usize) -> &mut Self {
+        self.max_body_bytes = max_bytes;
+        self
+    }
+
+    /// Skips reading any on-disk cache, forcing every URL to be fetched fresh.
+    pub fn with_no_cache(&mut self, enabled: bool) -> &mut Self {
+        self.no_cache = enabled;
+        self
+    }
+
+    /// Reads previously-resolved rows from `path` instead of `output_path`,
+    /// and writes each row resolved this run back to it too, so the cache can
+    /// live in a sidecar file separate from the CSV a run produces (e.g. when
+    /// `output_path` isn't in CSV format, or points at a different file each
+    /// run).
+    pub fn with_cache_path(&mut self, path: &'a Path) -> &mut Self {
+        self.cache_path = Some(path);
+        self
+    }
+
+    /// Caps how old a cached row can be and still be reused without hitting
+    /// the network at all. Without this, cached rows are still revalidated
+    /// with a conditional GET (see `get`) rather than trusted blindly.
+    pub fn with_max_cache_age(&mut self, max_age: Duration) -> &mut Self {
+        self.max_cache_age = Some(max_age);
+        self
+    }
+
+    /// Switches `run` over to `watch` instead of a single `write_csv_file` pass.
+    pub fn enable_watch_mode(&mut self, enabled: bool) -> &mut Self {
+        self.watch_mode = enabled;
+        self
+    }
+
+    /// Caps how many requests can be in flight to a single host at once,
+    /// independent of `max_threads`, so a batch of URLs sharing a host (e.g.
+    /// a run of t.co/twitter.com links) doesn't hammer it.
+    pub fn with_max_per_host(&mut self, max_per_host: usize) -> &mut Self {
+        self.max_per_host = max_per_host;
+        self
+    }
+
+    /// Minimum interval to wait between successive requests to the same host.
+    pub fn with_host_delay(&mut self, delay: Duration) -> &mut Self {
+        self.host_delay = Some(delay);
+        self
+    }
+
+    /// Selects the serialization used by `write_csv_file`/`write_to`.
+    pub fn with_format(&mut self, format: OutputFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
     fn processed_urls(&self) -> HashMap<String, HashMap<&'static str, Option<String>>> {
         let mut res = HashMap::new();
 
-        if self.output_path.exists() {
-            if let Some(mut reader) = csv::Reader::from_path(self.output_path).ok() {
+        if self.no_cache {
+            return res;
+        }
+
+        let cache_path = self.cache_path.unwrap_or(self.output_path);
+
+        if cache_path.exists() {
+            if let Some(mut reader) = csv::Reader::from_path(cache_path).ok() {
                 for row in reader.deserialize() {
                     if row.is_err() {
                         continue;
@@ -141,10 +559,14 @@ impl<'a> TitleGrabber<'a> {
 
                     let r: CsvRow = row.unwrap();
                     if r.page_title.is_some() || r.article_title.is_some() {
-                        let mut url_data = HashMap::with_capacity(3);
+                        let mut url_data = HashMap::with_capacity(7);
                         url_data.insert(END_URL_HEAD, Some(r.end_url));
                         url_data.insert(PAGE_TIT_HEAD, r.page_title);
                         url_data.insert(ART_TIT_HEAD, r.article_title);
+                        url_data.insert(ETAG_HEAD, r.etag);
+                        url_data.insert(LAST_MOD_HEAD, r.last_modified);
+                        url_data.insert(CONTENT_TYPE_HEAD, r.content_type);
+                        url_data.insert(CACHED_AT_HEAD, r.cached_at.map(|t| t.to_string()));
 
                         // let url_data = [
                         //     (END_URL_HEAD, Some(r.end_url)),
@@ -164,41 +586,303 @@ impl<'a> TitleGrabber<'a> {
         res
     }
 
+    // Reads the response body up to `max_body_bytes`, sniffing its content
+    // type from the first bytes when the `Content-Type` header is missing or
+    // generic. Non-HTML bodies stop being read as soon as the type is known,
+    // so a link to a large binary can't stall a worker.
+    async fn read_body(
+        &self,
+        resp: reqwest::Response,
+        declared_content_type: &Option<String>,
+    ) -> SniffedBody {
+        let declared = declared_content_type.as_deref().map(normalize_content_type);
+        let is_html_declared = declared
+            .as_deref()
+            .map_or(false, |ct| HTML_CONTENT_TYPES.contains(&ct));
+        let is_generic_or_missing = declared
+            .as_deref()
+            .map_or(true, |ct| GENERIC_CONTENT_TYPES.contains(&ct));
+
+        if !is_html_declared && !is_generic_or_missing {
+            return SniffedBody::Other(declared.unwrap());
+        }
+
+        let mut body: Vec<u8> = vec![];
+        let mut stream = resp.bytes_stream();
+        let mut truncated = false;
+
+        while let Some(chunk) = stream.next().await {
+            if let Some(chunk) = chunk.ok() {
+                body.extend_from_slice(&chunk);
+
+                if body.len() >= self.max_body_bytes {
+                    truncated = true;
+                    break;
+                }
+
+                if is_generic_or_missing && body.len() >= SNIFF_BYTES {
+                    let sniffed = sniff_content_type(&body);
+                    if sniffed != "text/html" {
+                        return SniffedBody::Other(sniffed);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+
+        if truncated {
+            warn!(
+                "Body exceeded max_body_bytes ({}) - truncating",
+                self.max_body_bytes
+            );
+        }
+
+        if is_html_declared || looks_like_html(&body) {
+            SniffedBody::Html(String::from_utf8_lossy(&body).into_owned())
+        } else {
+            SniffedBody::Other(sniff_content_type(&body))
+        }
+    }
+
+    // Parses a Netscape (`cookies.txt`) or JSON cookie file into a jar that
+    // can seed the HTTP client, so users can pre-authenticate against
+    // login/consent-gated hosts.
+    fn seed_cookie_jar(&self, path: &Path) -> reqwest::cookie::Jar {
+        let jar = reqwest::cookie::Jar::default();
+
+        if let Some(contents) = fs::read_to_string(path).ok() {
+            let entries: Option<Vec<CookieFileEntry>> = serde_json::from_str(&contents).ok();
+
+            if let Some(entries) = entries {
+                for entry in entries {
+                    if let Some(url) = Url::parse(&entry.url).ok() {
+                        jar.add_cookie_str(&format!("{}={}", entry.name, entry.value), &url);
+                    }
+                }
+            } else {
+                for line in contents.lines() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+
+                    let fields: Vec<&str> = line.split('\t').collect();
+                    if fields.len() != 7 {
+                        continue;
+                    }
+
+                    let domain = fields[0].trim_start_matches('.');
+                    let secure = fields[3].eq_ignore_ascii_case("TRUE");
+                    let scheme = if secure { "https" } else { "http" };
+                    let url_str = format!("{}://{}{}", scheme, domain, fields[2]);
+
+                    if let Some(url) = Url::parse(&url_str).ok() {
+                        jar.add_cookie_str(&format!("{}={}", fields[5], fields[6]), &url);
+                    }
+                }
+            }
+        } else {
+            warn!("Unable to read cookie file: {}", path.display());
+        }
+
+        jar
+    }
+
     fn build_http_client(&self) -> reqwest::Client {
-        reqwest::Client::builder()
+        let mut builder = reqwest::Client::builder()
             .timeout(Duration::from_secs(self.read_timeout))
             .connect_timeout(Duration::from_secs(self.connect_timeout))
-            .redirect(reqwest::RedirectPolicy::limited(self.max_redirects))
-            .build()
-            .unwrap()
+            .redirect(reqwest::redirect::Policy::limited(self.max_redirects))
+            .gzip(self.accepted_encodings.contains(&Encoding::Gzip))
+            .brotli(self.accepted_encodings.contains(&Encoding::Brotli))
+            .deflate(self.accepted_encodings.contains(&Encoding::Deflate))
+            .zstd(self.accepted_encodings.contains(&Encoding::Zstd));
+
+        if self.cookies_enabled {
+            builder = match self.cookie_file {
+                Some(path) => builder.cookie_provider(Arc::new(self.seed_cookie_jar(path))),
+                None => builder.cookie_store(true),
+            };
+        }
+
+        builder.build().unwrap()
+    }
+
+    /// Whether a response's status warrants a retry: 429 and 5xx are assumed
+    /// transient, everything else (including other 4xx like 404) is not.
+    fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+        status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// A server-provided `Retry-After` delay, if present, in either of the
+    /// two forms RFC 7231 §7.1.3 allows: delay-seconds (`"120"`) or an
+    /// IMF-fixdate HTTP-date (`"Sun, 06 Nov 1994 08:49:37 GMT"`). A date in
+    /// the past (clock skew, or the header arriving just after its own
+    /// deadline) yields a zero delay rather than `None`, so it still counts
+    /// as a server-provided hint for `get`'s `.max(self.backoff_delay(...))`.
+    fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+        let value = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())?
+            .trim();
+
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+
+        Self::parse_http_date(value)
+            .map(|at| at.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+
+    /// Parses the IMF-fixdate form of an HTTP-date (e.g.
+    /// `"Sun, 06 Nov 1994 08:49:37 GMT"`) - the only form RFC 7231 requires
+    /// senders to generate, though readers should also accept the obsolete
+    /// RFC 850 and asctime forms, which aren't handled here. Dependency-free:
+    /// computes days-since-epoch itself rather than pulling in a date crate.
+    fn parse_http_date(value: &str) -> Option<SystemTime> {
+        let rest = value.strip_suffix("GMT")?.trim_end();
+        let (_weekday, rest) = rest.split_once(", ")?;
+        let mut parts = rest.split_whitespace();
+
+        let day: i64 = parts.next()?.parse().ok()?;
+        let month = Self::month_number(parts.next()?)?;
+        // Bounds the year well within i64's safe range for the
+        // multiplications in `days_from_civil` below, so a malformed or
+        // hostile header (an attacker-controlled site's response) can't
+        // overflow them - not just a plausibility check on the date itself.
+        let year: i64 = parts.next()?.parse().ok()?;
+        if !(0..=9999).contains(&year) {
+            return None;
+        }
+
+        let mut time = parts.next()?.split(':');
+        let hour: i64 = time.next()?.parse().ok()?;
+        let min: i64 = time.next()?.parse().ok()?;
+        let sec: i64 = time.next()?.parse().ok()?;
+
+        if time.next().is_some() || parts.next().is_some() {
+            return None;
+        }
+
+        if !(1..=31).contains(&day) || !(0..24).contains(&hour) || !(0..60).contains(&min) || !(0..60).contains(&sec) {
+            return None;
+        }
+
+        let secs_since_epoch =
+            Self::days_from_civil(year, month, day) * 86_400 + hour * 3_600 + min * 60 + sec;
+
+        if secs_since_epoch < 0 {
+            return None;
+        }
+
+        Some(UNIX_EPOCH + Duration::from_secs(secs_since_epoch as u64))
+    }
+
+    fn month_number(name: &str) -> Option<i64> {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        MONTHS
+            .iter()
+            .position(|&month| month == name)
+            .map(|index| index as i64 + 1)
     }
 
-    fn get(&self, url: &str, http_client: &Arc<reqwest::Client>) -> Option<reqwest::Response> {
+    /// Howard Hinnant's "days from civil" algorithm: days between the Unix
+    /// epoch and the given proleptic-Gregorian (year, month, day), with no
+    /// dependency on a date/time crate.
+    fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = if y >= 0 { y } else { y - 399 } / 400;
+        let yoe = y - era * 400;
+        let mp = (month + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+        era * 146_097 + doe - 719_468
+    }
+
+    /// `backoff_base * 2^attempt`, capped at `max_backoff`, with equal
+    /// jitter (half the capped delay, plus a random extra up to that same
+    /// half) so a burst of retries against one host doesn't stay in lockstep.
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let capped_ms = self
+            .backoff_base
+            .as_millis()
+            .saturating_mul(2u128.saturating_pow(attempt))
+            .min(self.max_backoff.as_millis()) as u64;
+        let half_ms = capped_ms / 2;
+        let jitter_ms = if half_ms == 0 {
+            0
+        } else {
+            rand::thread_rng().gen_range(0..=half_ms)
+        };
+
+        Duration::from_millis(half_ms + jitter_ms)
+    }
+
+    async fn get(
+        &self,
+        url: &str,
+        http_client: &Arc<reqwest::Client>,
+        validators: Option<(&Option<String>, &Option<String>)>,
+    ) -> Option<reqwest::Response> {
+        let build_req = || {
+            let mut req = http_client.get(url);
+
+            if let Some((etag, last_modified)) = validators {
+                if let Some(etag) = etag {
+                    req = req.header(IF_NONE_MATCH_HEADER, etag.as_str());
+                }
+
+                if let Some(last_modified) = last_modified {
+                    req = req.header(IF_MODIFIED_SINCE_HEADER, last_modified.as_str());
+                }
+            }
+
+            req
+        };
+
         let mut retries = 0;
-        let mut res = http_client.get(url).send();
+        let mut res = build_req().send().await;
 
-        while let Some(err) = res.as_ref().err() {
+        loop {
             if retries >= self.max_retries {
                 break;
             }
 
-            retries += 1;
+            let retry_after = match &res {
+                Ok(resp) if Self::is_retryable_status(resp.status()) => Self::retry_after(resp),
+                Ok(_) => break,
+                Err(err) if err.is_connect() || err.is_timeout() => None,
+                Err(_) => break,
+            };
 
-            let will_retry = (err.is_http() || err.is_timeout() || err.is_server_error())
-                && retries < self.max_retries;
-
-            if will_retry {
-                if let Some(status) = err.status() {
-                    warn!("GET {} {} - Retry: {}", url, status, retries);
-                } else {
-                    warn!("GET {} Err: {} - Retry: {}", url, err, retries);
-                }
+            retries += 1;
 
-                thread::sleep(Duration::from_secs(retries));
-                res = http_client.get(url).send();
-            } else {
-                break;
+            // A server-provided `Retry-After` is honored as-is, uncapped -
+            // `max_backoff` only bounds the computed exponential backoff
+            // below (already applied inside `backoff_delay`), not a delay
+            // the server explicitly asked for.
+            let delay = retry_after
+                .unwrap_or_default()
+                .max(self.backoff_delay(retries as u32));
+
+            match &res {
+                Ok(resp) => warn!(
+                    "GET {} {} - Retry: {} in {:?}",
+                    url,
+                    resp.status(),
+                    retries,
+                    delay
+                ),
+                Err(err) => warn!("GET {} Err: {} - Retry: {} in {:?}", url, err, retries, delay),
             }
+
+            tokio::time::sleep(delay).await;
+            res = build_req().send().await;
         }
 
         match res {
@@ -207,189 +891,657 @@ impl<'a> TitleGrabber<'a> {
                 Some(resp)
             }
             Err(err) => {
-                if let Some(status) = err.status() {
-                    error!("GET {} {} - Retry: {}", url, status, retries);
-                } else {
-                    error!("GET {} Err: {} - Retry: {}", url, err, retries);
-                }
+                error!("GET {} Err: {} - Retry: {}", url, err, retries);
 
                 None
             }
         }
     }
 
-    fn scrape_url(
+    fn cache_is_fresh(&self, row: &HashMap<&'static str, Option<String>>) -> bool {
+        let max_age = match self.max_cache_age {
+            Some(max_age) => max_age,
+            None => return false,
+        };
+
+        let cached_at = row
+            .get(CACHED_AT_HEAD)
+            .cloned()
+            .flatten()
+            .and_then(|v| v.parse::<u64>().ok());
+
+        match cached_at {
+            Some(secs) => SystemTime::now()
+                .duration_since(UNIX_EPOCH + Duration::from_secs(secs))
+                .map_or(false, |age| age <= max_age),
+            None => false,
+        }
+    }
+
+    async fn scrape_url(
         &self,
         url: String,
         http_client: Arc<reqwest::Client>,
-        tx: mpsc::Sender<Option<CsvRow>>,
-    ) {
+        cached: Option<HashMap<&'static str, Option<String>>>,
+        primary_host: Option<&str>,
+        host_throttle: &Arc<HostThrottle>,
+    ) -> Option<CsvRow> {
+        if cached
+            .as_ref()
+            .map_or(false, |row| self.cache_is_fresh(row))
+        {
+            info!("GET {} - fresh cache hit, skipping network", url);
+            return cached.map(|row| row_from_cache(url, row));
+        }
+
         let mut ret = None;
+        let validators = cached
+            .as_ref()
+            .map(|row| (&row[ETAG_HEAD], &row[LAST_MOD_HEAD]));
+
+        if let Some(resp) = self.get(&url, &http_client, validators).await {
+            if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+                info!("GET {} - 304 Not Modified, reusing cached row", url);
+
+                return cached.map(|row| row_from_cache(url, row));
+            }
 
-        if let Some(resp) = self.get(&url, &http_client) {
+            let etag = resp
+                .headers()
+                .get(reqwest::header::ETAG)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_owned());
+            let last_modified = resp
+                .headers()
+                .get(reqwest::header::LAST_MODIFIED)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_owned());
             let res = resp.error_for_status();
 
-            if let Some(mut resp) = res.ok() {
-                if let Some(html) = resp.text().ok() {
-                    let mut end_url = resp.url().clone().into_string();
-                    debug!("GET {} - {} bytes", end_url, html.len());
-
-                    let doc = Html::parse_document(&html);
-
-                    let mut tweet_urls = vec![];
-                    for tweet_txt_sel in TWEET_TXT_SELS.iter() {
-                        let css_sel_str = format!("{} {} a", TWEET_PERMA_LINK_SEL, tweet_txt_sel);
-                        let css_sel = Selector::parse(&css_sel_str).unwrap();
-                        let mut links = doc
-                            .select(&css_sel)
-                            .filter_map(|a| a.value().attr("href"))
-                            .collect();
-                        tweet_urls.append(&mut links);
+            if let Some(resp) = res.ok() {
+                let mut end_url = resp.url().clone().into_string();
+                let declared_content_type = resp
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_owned());
+
+                match self.read_body(resp, &declared_content_type).await {
+                    SniffedBody::Other(content_type) => {
+                        debug!("GET {} - non-HTML body ({})", end_url, content_type);
+
+                        ret.replace(CsvRow {
+                            url,
+                            end_url,
+                            page_title: None,
+                            article_title: None,
+                            etag,
+                            last_modified,
+                            content_type: Some(content_type),
+                            cached_at: Some(now_unix_secs()),
+                        });
                     }
-                    tweet_urls.retain(|&url| !url.is_empty());
-                    tweet_urls.sort_unstable();
-                    tweet_urls.dedup_by_key(|url| *url);
-                    let tweet_urls = tweet_urls.into_iter().filter_map(|url| {
-                        let mut ret = Some(url.to_owned());
-
-                        if URL_RE.is_match(url) {
-                            if let Some(resp) = self.get(url, &http_client) {
-                                let end_url = resp.url();
-                                let _opt = ret.replace(end_url.clone().into_string());
-
-                                if let Some(host) = end_url.host_str() {
-                                    if host == TWITTER_HOST {
-                                        if !TWITTER_STATUS_RE.is_match(end_url.as_str()) {
-                                            let _opt = ret.take();
+                    SniffedBody::Html(html) => {
+                        debug!("GET {} - {} bytes", end_url, html.len());
+
+                        let doc = Html::parse_document(&html);
+
+                        let mut tweet_urls = vec![];
+                        for tweet_txt_sel in TWEET_TXT_SELS.iter() {
+                            let css_sel_str =
+                                format!("{} {} a", TWEET_PERMA_LINK_SEL, tweet_txt_sel);
+                            let css_sel = Selector::parse(&css_sel_str).unwrap();
+                            let mut links = doc
+                                .select(&css_sel)
+                                .filter_map(|a| a.value().attr("href"))
+                                .collect();
+                            tweet_urls.append(&mut links);
+                        }
+                        tweet_urls.retain(|&url| !url.is_empty());
+                        tweet_urls.sort_unstable();
+                        tweet_urls.dedup_by_key(|url| *url);
+
+                        let mut followed_tweet_urls = vec![];
+                        for url in tweet_urls.into_iter() {
+                            let mut ret = Some(url.to_owned());
+
+                            if URL_RE.is_match(url) {
+                                // The t.co link and the twitter.com/etc it
+                                // resolves to are (usually) different hosts,
+                                // so this needs its own permit keyed on the
+                                // followed URL's host - the caller's permit
+                                // (write_async) is keyed on the *primary*
+                                // URL's host and doesn't cover this one. Skip
+                                // acquiring a second permit when the hosts
+                                // match: the outer permit already throttles
+                                // that host, and re-acquiring from the same
+                                // exhausted pool while still holding it would
+                                // deadlock.
+                                let followed_host = host_of(url);
+                                let _followed_host_permit = if followed_host.as_deref() == primary_host
+                                {
+                                    None
+                                } else {
+                                    match &followed_host {
+                                        Some(host) => Some(host_throttle.acquire(host).await),
+                                        None => None,
+                                    }
+                                };
+
+                                if let Some(resp) = self.get(url, &http_client, None).await {
+                                    let end_url = resp.url();
+                                    let _opt = ret.replace(end_url.clone().into_string());
+
+                                    if let Some(host) = end_url.host_str() {
+                                        if host == TWITTER_HOST {
+                                            if !TWITTER_STATUS_RE.is_match(end_url.as_str()) {
+                                                let _opt = ret.take();
+                                            }
                                         }
                                     }
                                 }
                             }
-                        }
 
-                        ret
-                    });
-                    let tweet_urls = tweet_urls.filter_map(|url| {
-                        if url.starts_with("/") {
-                            TWITTER_URL_PREFIX.join(&url).ok()
-                        } else {
-                            Url::parse(&url).ok()
+                            if let Some(url) = ret {
+                                followed_tweet_urls.push(url);
+                            }
                         }
-                    });
-                    let tweet_urls = tweet_urls.filter_map(|url| {
-                        let mut ret = Some(url.clone().into_string());
-
-                        if let Some(host) = url.host_str() {
-                            if host == TWITTER_HOST {
-                                let fwd_slash_cnt =
-                                    url.path().chars().filter(|&c| c == '/').count();
-                                if fwd_slash_cnt > 1 {
-                                    if !TWITTER_STATUS_RE.is_match(url.as_str()) {
-                                        let _opt = ret.take();
+                        let tweet_urls = followed_tweet_urls.into_iter().filter_map(|url| {
+                            if url.starts_with("/") {
+                                TWITTER_URL_PREFIX.join(&url).ok()
+                            } else {
+                                Url::parse(&url).ok()
+                            }
+                        });
+                        let tweet_urls = tweet_urls.filter_map(|url| {
+                            let mut ret = Some(url.clone().into_string());
+
+                            if let Some(host) = url.host_str() {
+                                if host == TWITTER_HOST {
+                                    let fwd_slash_cnt =
+                                        url.path().chars().filter(|&c| c == '/').count();
+                                    if fwd_slash_cnt > 1 {
+                                        if !TWITTER_STATUS_RE.is_match(url.as_str()) {
+                                            let _opt = ret.take();
+                                        }
                                     }
                                 }
                             }
-                        }
 
-                        ret
-                    });
-                    let mut tweet_urls: std::vec::Vec<_> = tweet_urls.collect();
-                    tweet_urls.sort_unstable();
-                    if !tweet_urls.is_empty() {
-                        end_url = itertools::join(tweet_urls.into_iter(), CSV_FIELD_SEP);
-                    }
+                            ret
+                        });
+                        let mut tweet_urls: std::vec::Vec<_> = tweet_urls.collect();
+                        tweet_urls.sort_unstable();
+                        if !tweet_urls.is_empty() {
+                            end_url = itertools::join(tweet_urls.into_iter(), CSV_FIELD_SEP);
+                        }
 
-                    let mut page_tit = None;
-                    if let Some(page_tit_el) = doc.select(&PAGE_TIT_SEL).next() {
-                        page_tit.replace(fix_whitespace(page_tit_el.inner_html()));
-                    }
+                        let mut page_tit = None;
+                        if let Some(page_tit_el) = doc.select(&PAGE_TIT_SEL).next() {
+                            page_tit.replace(fix_whitespace(page_tit_el.inner_html()));
+                        }
 
-                    let mut art_tit = None;
-                    if let Some(art_tit_el) = doc.select(&ART_HEAD_SEL).next() {
-                        art_tit.replace(fix_whitespace(itertools::join(art_tit_el.text(), " ")));
-                    } else {
-                        if let Some(art_tit_el) = doc.select(&DOC_TIT_SEL).next() {
+                        let mut art_tit = None;
+                        if let Some(art_tit_el) = doc.select(&ART_HEAD_SEL).next() {
                             art_tit
                                 .replace(fix_whitespace(itertools::join(art_tit_el.text(), " ")));
+                        } else {
+                            if let Some(art_tit_el) = doc.select(&DOC_TIT_SEL).next() {
+                                art_tit.replace(fix_whitespace(itertools::join(
+                                    art_tit_el.text(),
+                                    " ",
+                                )));
+                            }
                         }
-                    }
 
-                    ret.replace(CsvRow {
-                        url: url,
-                        end_url: end_url.to_owned(),
-                        page_title: page_tit,
-                        article_title: art_tit,
-                    });
+                        ret.replace(CsvRow {
+                            url: url,
+                            end_url: end_url.to_owned(),
+                            page_title: page_tit,
+                            article_title: art_tit,
+                            etag,
+                            last_modified,
+                            content_type: Some(HTML_CONTENT_TYPES[0].to_owned()),
+                            cached_at: Some(now_unix_secs()),
+                        });
+                    }
                 }
             }
         };
 
-        let _res = tx.send(ret);
+        ret
     }
 
-    pub fn write_csv_file(&self) -> Result<(), Box<Error>> {
-        let processed_urls = self.processed_urls();
+    async fn write_async<W: Write>(
+        &self,
+        writer: W,
+        format: OutputFormat,
+        processed_urls: HashMap<String, HashMap<&'static str, Option<String>>>,
+        changed_urls: Option<&HashSet<String>>,
+        mut on_row: impl FnMut(Option<&CsvRow>, usize, usize, usize),
+    ) -> Result<(), Box<Error>> {
+        // Sidecar cache file, mirroring `output_path` content plus validators
+        // so a future run's `processed_urls()` (read by callers *before*
+        // this function is entered, and thus before `output_path` gets
+        // truncated) has somewhere distinct from `output_path` to read from.
+        let cache_path = self
+            .cache_path
+            .filter(|&cache_path| !Self::same_file(cache_path, self.output_path));
+        let mut cache_writer = match cache_path {
+            Some(cache_path) => Some(csv::Writer::from_path(cache_path)?),
+            None => None,
+        };
         let http_client = Arc::new(self.build_http_client());
-        let mut writer = csv::Writer::from_path(self.output_path)?;
-        let mut pool = Pool::new(self.max_threads as u32);
-        let work_queue = Arc::new(AtomicUsize::new(0));
-        let (tx, rx) = mpsc::channel();
+        let semaphore = Arc::new(Semaphore::new(self.max_threads));
+        let host_throttle = Arc::new(HostThrottle::new(self.max_per_host, self.host_delay));
+        let mut urls = vec![];
 
-        pool.scoped(|scoped| {
-            for path in self.files.iter() {
-                debug!("FILE: {}", path.display());
-
-                if let Some(file) = File::open(path).ok() {
-                    let reader = BufReader::new(file);
-
-                    for line in reader.lines() {
-                        if let Some(line) = line.ok() {
-                            if let Some(match_) = URL_RE.find(&line) {
-                                let url = match_.as_str();
-
-                                if let Some(row) = processed_urls.get(url) {
-                                    // HashMap<String, HashMap<&'static str, Option<String>>>
-                                    let res = writer.serialize(CsvRow {
-                                        url: url.to_owned(),
-                                        end_url: row.get(END_URL_HEAD).cloned().unwrap().unwrap(),
-                                        page_title: row.get(PAGE_TIT_HEAD).cloned().unwrap(),
-                                        article_title: row.get(ART_TIT_HEAD).cloned().unwrap(),
-                                    });
-
-                                    if let Some(_) = res.err() {
-                                        error!(
-                                            "Failed to reuse data for previously scraped URL: {}",
-                                            url
-                                        );
-                                    }
-                                } else {
-                                    let url = url.to_owned();
-                                    let http_client = http_client.clone();
-                                    let tx = tx.clone();
-                                    let work_queue = work_queue.clone();
-
-                                    scoped.execute(move || {
-                                        self.scrape_url(url, http_client, tx);
-                                        work_queue.fetch_add(1, Ordering::SeqCst);
-                                    });
-                                }
-                            }
+        for path in self.files.iter() {
+            debug!("FILE: {}", path.display());
+
+            if let Some(file) = File::open(path).ok() {
+                let reader = BufReader::new(file);
+
+                for line in reader.lines() {
+                    if let Some(line) = line.ok() {
+                        if let Some(match_) = URL_RE.find(&line) {
+                            urls.push(match_.as_str().to_owned());
                         }
                     }
                 }
             }
+        }
+
+        let total = urls.len();
+        let mut processed = 0;
+        let mut failures = 0;
+        // Rows resolved this run, keyed by URL; merged back over the stale
+        // `processed_urls` cache below so writing through to `cache_path`
+        // doesn't drop entries for URLs that weren't part of this run's
+        // input files. Left empty (and never populated below) when there's
+        // no cache_writer, so a plain run doesn't pay to buffer every row.
+        let mut resolved_rows: HashMap<String, CsvRow> = HashMap::new();
+
+        let mut results = stream::iter(urls.into_iter())
+            .map(|url| {
+                let cached = processed_urls.get(&url).cloned();
+                // In watch mode, a changed-files pass only reprocesses URLs
+                // whose line is new or changed; any other URL with a cached
+                // row is served straight from it without touching the
+                // network. No `changed_urls` (a one-shot run, or watch's
+                // initial pass) reprocesses everything, same as before.
+                let needs_scrape =
+                    changed_urls.map_or(true, |changed| changed.contains(&url)) || cached.is_none();
+                let http_client = http_client.clone();
+                let semaphore = semaphore.clone();
+                let host_throttle = host_throttle.clone();
+
+                async move {
+                    if !needs_scrape {
+                        return cached.map(|row| row_from_cache(url, row));
+                    }
+
+                    let _permit = semaphore.acquire().await.unwrap();
+                    let host = host_of(&url);
+                    let _host_permit = match &host {
+                        Some(host) => Some(host_throttle.acquire(host).await),
+                        None => None,
+                    };
+
+                    self.scrape_url(url, http_client, cached, host.as_deref(), &host_throttle)
+                        .await
+                }
+            })
+            // The semaphore is the real concurrency gate; buffer_unordered just
+            // needs enough slack to keep it saturated with in-flight futures.
+            .buffer_unordered(self.max_threads * 4);
+
+        match format {
+            OutputFormat::Csv => {
+                let mut csv_writer = csv::Writer::from_writer(writer);
+
+                while let Some(row) = results.next().await {
+                    processed += 1;
+                    if row.is_none() {
+                        failures += 1;
+                    }
+                    on_row(row.as_ref(), processed, total, failures);
+
+                    if let Some(row) = row {
+                        csv_writer.serialize(&row)?;
+
+                        if cache_writer.is_some() {
+                            resolved_rows.insert(row.url().to_owned(), row);
+                        }
+                    }
+                }
+
+                csv_writer.flush()?;
+            }
+            OutputFormat::Ndjson => {
+                let mut writer = writer;
+
+                while let Some(row) = results.next().await {
+                    processed += 1;
+                    if row.is_none() {
+                        failures += 1;
+                    }
+                    on_row(row.as_ref(), processed, total, failures);
+
+                    if let Some(row) = row {
+                        serde_json::to_writer(&mut writer, &row.as_json_record())?;
+                        writer.write_all(b"\n")?;
+
+                        if cache_writer.is_some() {
+                            resolved_rows.insert(row.url().to_owned(), row);
+                        }
+                    }
+                }
+            }
+            OutputFormat::Json => {
+                let mut writer = writer;
+                let mut wrote_any = false;
+
+                writer.write_all(b"[")?;
+
+                while let Some(row) = results.next().await {
+                    processed += 1;
+                    if row.is_none() {
+                        failures += 1;
+                    }
+                    on_row(row.as_ref(), processed, total, failures);
+
+                    if let Some(row) = row {
+                        if wrote_any {
+                            writer.write_all(b",")?;
+                        }
+
+                        serde_json::to_writer(&mut writer, &row.as_json_record())?;
+                        wrote_any = true;
+
+                        if cache_writer.is_some() {
+                            resolved_rows.insert(row.url().to_owned(), row);
+                        }
+                    }
+                }
+
+                writer.write_all(b"]")?;
+            }
+        }
+
+        // The stream's closures hold the last borrow of `processed_urls`;
+        // drop it so the cache merge below can consume that map by value.
+        drop(results);
+
+        if let Some(mut cache_writer) = cache_writer {
+            let mut cache_rows: HashMap<String, CsvRow> = processed_urls
+                .into_iter()
+                .map(|(url, row)| (url.clone(), row_from_cache(url, row)))
+                .collect();
+            cache_rows.extend(resolved_rows);
+
+            for row in cache_rows.into_values() {
+                cache_writer.serialize(&row)?;
+            }
+
+            cache_writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether `a` and `b` resolve to the same on-disk file, using
+    /// canonicalized paths when both exist so e.g. a relative and absolute
+    /// spelling of `--cache-path`/`--output` aren't mistaken for distinct
+    /// files (which would open two independent writers onto the same file
+    /// and corrupt it). Falls back to plain path equality when either side
+    /// can't be canonicalized (e.g. `cache_path` doesn't exist yet).
+    fn same_file(a: &Path, b: &Path) -> bool {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        }
+    }
+
+    /// Runs the scrape pipeline and serializes each resolved record as it
+    /// arrives, writing to any `Write` sink (a file, stdout, an in-memory
+    /// buffer...) in the given `format`.
+    pub fn write_to<W: Write>(&self, writer: W, format: OutputFormat) -> Result<(), Box<Error>> {
+        // Read any on-disk cache before `writer` (which may itself be
+        // `output_path` truncated by a caller) gets written to.
+        let processed_urls = self.processed_urls();
+        Runtime::new()?.block_on(self.write_async(writer, format, processed_urls, None, |_, _, _, _| {}))
+    }
+
+    async fn write_output_file_async(
+        &self,
+        changed_urls: Option<&HashSet<String>>,
+        on_row: impl FnMut(Option<&CsvRow>, usize, usize, usize),
+    ) -> Result<(), Box<Error>> {
+        // Load the cache (which by default *is* `output_path`) before
+        // `File::create` truncates it - otherwise every run starts from an
+        // empty cache and never skips or revalidates a previously-seen URL.
+        let processed_urls = self.processed_urls();
+        let file = File::create(self.output_path)?;
+        self.write_async(file, self.format, processed_urls, changed_urls, on_row)
+            .await
+    }
+
+    /// A thin consumer of `stream`: drains the receiver, propagating the
+    /// first fatal error it reports (if any) back to the caller.
+    pub fn write_csv_file(&self) -> Result<(), Box<Error>> {
+        for item in self.stream()? {
+            if let Some(error) = item.error {
+                return Err(error.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds the `ConfigSnapshot` `stream` moves into its background thread.
+    fn config_snapshot(&self) -> ConfigSnapshot {
+        ConfigSnapshot {
+            files: self.files.iter().map(|&path| path.to_path_buf()).collect(),
+            output_path: self.output_path.to_path_buf(),
+            connect_timeout: self.connect_timeout,
+            read_timeout: self.read_timeout,
+            max_redirects: self.max_redirects,
+            max_retries: self.max_retries,
+            backoff_base: self.backoff_base,
+            max_backoff: self.max_backoff,
+            max_threads: self.max_threads,
+            accepted_encodings: self.accepted_encodings.clone(),
+            cookies_enabled: self.cookies_enabled,
+            cookie_file: self.cookie_file.map(Path::to_path_buf),
+            max_body_bytes: self.max_body_bytes,
+            no_cache: self.no_cache,
+            cache_path: self.cache_path.map(Path::to_path_buf),
+            max_cache_age: self.max_cache_age,
+            watch_mode: self.watch_mode,
+            max_per_host: self.max_per_host,
+            host_delay: self.host_delay,
+            format: self.format,
+        }
+    }
+
+    /// Runs a single one-shot scrape-and-write pass (regardless of
+    /// `watch_mode` - same scope as `write_csv_file`, not `run`/`watch`) on a
+    /// background thread, returning a receiver that yields a `StreamItem`
+    /// for each record as soon as it resolves instead of buffering the whole
+    /// run in memory before a caller sees anything. `write_csv_file` is
+    /// built on top of this.
+    pub fn stream(&self) -> Result<mpsc::Receiver<StreamItem>, Box<Error>> {
+        let snapshot = self.config_snapshot();
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            // Borrowed from `snapshot` only now that it's already living in
+            // this closure's own frame, so `owned` never outlives (or needs
+            // to be moved alongside) the data it borrows.
+            let owned = snapshot.borrow();
+
+            // Caught so a panic deep in the worker pool (e.g. one of the
+            // `.unwrap()`s around lock/semaphore acquisition) surfaces as an
+            // error on the channel instead of silently dropping `tx` and
+            // letting `write_csv_file` read that as a clean, empty run.
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+                Runtime::new()
+                    .map_err(|err| -> Box<Error> { Box::new(err) })
+                    .and_then(|rt| {
+                        rt.block_on(owned.write_output_file_async(None, |row, processed, total, failures| {
+                            let _ = tx.send(StreamItem {
+                                row: row.cloned(),
+                                processed,
+                                total,
+                                failures,
+                                error: None,
+                            });
+                        }))
+                    })
+            }));
+
+            let error = match outcome {
+                Ok(Ok(())) => None,
+                Ok(Err(err)) => Some(err.to_string()),
+                Err(payload) => Some(Self::describe_panic(payload)),
+            };
+
+            if let Some(error) = error {
+                let _ = tx.send(StreamItem {
+                    row: None,
+                    processed: 0,
+                    total: 0,
+                    failures: 0,
+                    error: Some(error),
+                });
+            }
         });
 
-        for _ in 0..work_queue.load(Ordering::Relaxed) {
-            if let Some(res) = rx.recv().ok() {
-                if let Some(row) = res {
-                    writer.serialize(row)?;
+        Ok(rx)
+    }
+
+    fn describe_panic(payload: Box<dyn std::any::Any + Send>) -> String {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            format!("worker thread panicked: {}", message)
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            format!("worker thread panicked: {}", message)
+        } else {
+            "worker thread panicked".to_owned()
+        }
+    }
+
+    /// Runs `write_csv_file` once, then keeps the process alive and re-runs it
+    /// every time one of `files` is modified on disk. Changes are debounced by
+    /// `WATCH_DEBOUNCE_MS` so a burst of edits from a single save coalesces into
+    /// one run; only URLs on a new or changed line of the file(s) that
+    /// triggered the event are re-scraped, everything else is served from
+    /// the cache untouched. `on_row` is forwarded to every pass.
+    pub fn watch<F>(&self, on_row: F) -> Result<(), Box<Error>>
+    where
+        F: FnMut(Option<&CsvRow>, usize, usize, usize),
+    {
+        Runtime::new()?.block_on(self.watch_async(on_row))
+    }
+
+    /// Single entry point for callers that don't care whether `watch_mode` is
+    /// on: dispatches to `watch` or a one-shot `write_csv_file`-equivalent
+    /// accordingly, so a CLI can wire up the same progress callback either
+    /// way.
+    pub fn run<F>(&self, on_row: F) -> Result<(), Box<Error>>
+    where
+        F: FnMut(Option<&CsvRow>, usize, usize, usize),
+    {
+        if self.watch_mode {
+            self.watch(on_row)
+        } else {
+            Runtime::new()?.block_on(self.write_output_file_async(None, on_row))
+        }
+    }
+
+    async fn watch_async<F>(&self, mut on_row: F) -> Result<(), Box<Error>>
+    where
+        F: FnMut(Option<&CsvRow>, usize, usize, usize),
+    {
+        use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+        use std::sync::mpsc::channel;
+
+        self.write_output_file_async(None, &mut on_row).await?;
+
+        // Snapshots of each watched file's lines as of the last pass, so a
+        // change event can be diffed down to just the URLs that are new or
+        // whose line changed, instead of re-scraping every URL again.
+        let mut snapshots: HashMap<PathBuf, Vec<String>> = self
+            .files
+            .iter()
+            .map(|&path| (path.to_path_buf(), Self::read_lines(path)))
+            .collect();
+
+        let (tx, rx) = channel();
+        let mut watcher = watcher(tx, Duration::from_millis(WATCH_DEBOUNCE_MS))?;
+
+        for path in self.files.iter() {
+            watcher.watch(path, RecursiveMode::NonRecursive)?;
+        }
+
+        loop {
+            match rx.recv() {
+                Ok(DebouncedEvent::Write(path))
+                | Ok(DebouncedEvent::Create(path))
+                | Ok(DebouncedEvent::Rename(_, path)) => {
+                    let changed_urls = self.changed_urls(&mut snapshots, &path);
+                    info!(
+                        "{} changed - re-scraping {} URL(s)",
+                        path.display(),
+                        changed_urls.len()
+                    );
+                    self.write_output_file_async(Some(&changed_urls), &mut on_row)
+                        .await?;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    error!("File watcher error: {}", err);
+                    break;
                 }
             }
         }
 
         Ok(())
     }
+
+    fn read_lines(path: &Path) -> Vec<String> {
+        match File::open(path) {
+            Ok(file) => BufReader::new(file).lines().filter_map(|l| l.ok()).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Diffs `path`'s current lines against `snapshots`' record of its lines
+    /// as of the last pass (updating it in place), and returns the URL found
+    /// on any line that's new or whose content changed. A line that's
+    /// unchanged - even if other lines around it shifted - isn't considered
+    /// changed, since its URL couldn't have changed either.
+    fn changed_urls(&self, snapshots: &mut HashMap<PathBuf, Vec<String>>, path: &Path) -> HashSet<String> {
+        let new_lines = Self::read_lines(path);
+        let old_lines = snapshots.get(path).cloned().unwrap_or_default();
+
+        let mut changed = HashSet::new();
+        for i in 0..new_lines.len().max(old_lines.len()) {
+            if old_lines.get(i) == new_lines.get(i) {
+                continue;
+            }
+
+            if let Some(line) = new_lines.get(i) {
+                if let Some(match_) = URL_RE.find(line) {
+                    changed.insert(match_.as_str().to_owned());
+                }
+            }
+        }
+
+        snapshots.insert(path.to_path_buf(), new_lines);
+
+        changed
+    }
 }
 
 #[cfg(test)]
@@ -417,6 +1569,8 @@ mod tests {
         assert_eq!(READ_TO, instance.read_timeout);
         assert_eq!(MAX_REDIRECTS, instance.max_redirects);
         assert_eq!(MAX_RETRIES, instance.max_retries);
+        assert_eq!(Duration::from_millis(BACKOFF_BASE_MS), instance.backoff_base);
+        assert_eq!(Duration::from_millis(MAX_BACKOFF_MS), instance.max_backoff);
         assert_eq!(num_cpus::get(), instance.max_threads);
     }
 
@@ -464,6 +1618,207 @@ mod tests {
         assert_eq!(threads, instance.max_threads);
     }
 
+    #[test]
+    fn it_allows_tweaking_its_accepted_encodings() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        let encodings = vec![Encoding::Brotli, Encoding::Zstd];
+
+        instance.with_accepted_encodings(encodings.clone());
+
+        assert_eq!(encodings, instance.accepted_encodings);
+    }
+
+    #[test]
+    fn it_allows_enabling_cookies() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+
+        instance.with_cookies(true);
+
+        assert!(instance.cookies_enabled);
+    }
+
+    #[test]
+    fn it_allows_seeding_cookies_from_a_file() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        let cookie_file = Path::new("tests/fixtures/cookies.txt");
+
+        instance.with_cookie_file(cookie_file);
+
+        assert!(instance.cookies_enabled);
+        assert_eq!(Some(cookie_file), instance.cookie_file);
+    }
+
+    #[test]
+    fn it_allows_tweaking_its_max_body_bytes() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        let max_bytes = 1024;
+
+        instance.with_max_body_bytes(max_bytes);
+
+        assert_eq!(max_bytes, instance.max_body_bytes);
+    }
+
+    #[test]
+    fn it_allows_disabling_the_cache() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+
+        instance.with_no_cache(true);
+
+        assert!(instance.no_cache);
+        assert!(instance.processed_urls().is_empty());
+    }
+
+    #[test]
+    fn it_allows_tweaking_its_cache_path() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        let cache_path = Path::new("tests/fixtures/cache.csv");
+
+        instance.with_cache_path(cache_path);
+
+        assert_eq!(Some(cache_path), instance.cache_path);
+    }
+
+    #[test]
+    fn it_allows_tweaking_its_max_cache_age() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        let max_age = Duration::from_secs(3600);
+
+        instance.with_max_cache_age(max_age);
+
+        assert_eq!(Some(max_age), instance.max_cache_age);
+    }
+
+    #[test]
+    fn it_allows_enabling_watch_mode() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+
+        instance.enable_watch_mode(true);
+
+        assert!(instance.watch_mode);
+    }
+
+    #[test]
+    fn it_allows_tweaking_its_max_per_host() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+
+        instance.with_max_per_host(2);
+
+        assert_eq!(2, instance.max_per_host);
+    }
+
+    #[test]
+    fn it_allows_tweaking_its_host_delay() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        let delay = Duration::from_millis(500);
+
+        instance.with_host_delay(delay);
+
+        assert_eq!(Some(delay), instance.host_delay);
+    }
+
+    #[test]
+    fn it_allows_tweaking_its_format() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+
+        instance.with_format(OutputFormat::Ndjson);
+
+        assert_eq!(OutputFormat::Ndjson, instance.format);
+    }
+
+    #[test]
+    fn it_expands_the_end_url_redirect_chain_into_an_array_for_json_records() {
+        let row = CsvRow {
+            url: "https://t.co/abc".to_owned(),
+            end_url: "https://a.com,https://b.com".to_owned(),
+            page_title: Some("B".to_owned()),
+            article_title: None,
+            etag: None,
+            last_modified: None,
+            content_type: Some("text/html".to_owned()),
+            cached_at: None,
+        };
+
+        assert_eq!(
+            vec!["https://a.com", "https://b.com"],
+            row.as_json_record().end_url
+        );
+    }
+
+    #[test]
+    fn it_sniffs_content_type_from_the_body_when_undeclared() {
+        assert_eq!(
+            "text/html",
+            sniff_content_type(b"<!doctype html><title>Hi</title>")
+        );
+        assert_eq!("application/pdf", sniff_content_type(b"%PDF-1.4"));
+        assert_eq!(
+            "application/octet-stream",
+            sniff_content_type(b"\x00\x01\x02")
+        );
+    }
+
+    #[test]
+    fn it_caps_backoff_delay_at_max_backoff() {
+        env::set_var("TESTING", "1");
+        let mut instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        instance
+            .with_backoff_base(Duration::from_millis(100))
+            .with_max_backoff(Duration::from_millis(500));
+
+        // 100 * 2^0 = 100ms, well under the cap.
+        assert!(instance.backoff_delay(0) <= Duration::from_millis(100));
+        // 100 * 2^10 would be seconds; the delay (half the cap plus jitter up
+        // to that same half) must never exceed the 500ms cap.
+        assert!(instance.backoff_delay(10) <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn it_parses_the_http_date_form_of_retry_after() {
+        // The canonical RFC 7231 example; 784111777 is its known Unix
+        // timestamp.
+        let parsed = TitleGrabber::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(
+            784_111_777,
+            parsed.duration_since(UNIX_EPOCH).unwrap().as_secs()
+        );
+    }
+
+    #[test]
+    fn it_rejects_malformed_http_dates() {
+        assert!(TitleGrabber::parse_http_date("not a date").is_none());
+        assert!(TitleGrabber::parse_http_date("Sun, 06 Nov 1994 08:49:37").is_none());
+        assert!(TitleGrabber::parse_http_date("Sun, 06 Nov 1994 08:49 GMT").is_none());
+        // Out-of-range fields are rejected outright rather than wrapping
+        // into a bogus date, and a huge year can't overflow the arithmetic
+        // in `days_from_civil`.
+        assert!(TitleGrabber::parse_http_date("Sun, 99 Nov 1994 25:99:99 GMT").is_none());
+        assert!(TitleGrabber::parse_http_date("Sun, 06 Nov 63000000000000000 08:49:37 GMT").is_none());
+    }
+
+    #[test]
+    fn it_treats_429_and_5xx_as_retryable_but_not_404() {
+        assert!(TitleGrabber::is_retryable_status(
+            reqwest::StatusCode::TOO_MANY_REQUESTS
+        ));
+        assert!(TitleGrabber::is_retryable_status(
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(!TitleGrabber::is_retryable_status(
+            reqwest::StatusCode::NOT_FOUND
+        ));
+    }
+
     #[test]
     fn it_does_not_panic_on_file_not_found() {
         env::set_var("TESTING", "1");
@@ -511,6 +1866,57 @@ mod tests {
         assert!(fs::remove_file(out_path).is_ok());
     }
 
+    #[test]
+    fn it_only_reports_urls_on_new_or_changed_lines_as_changed() {
+        env::set_var("TESTING", "1");
+        let path = Path::new("tests/fixtures/watch_diff_urls.txt");
+        fs::write(
+            path,
+            "https://a.example/one\nhttps://b.example/two\nhttps://c.example/three\n",
+        )
+        .unwrap();
+
+        let instance = TitleGrabber::new(vec![], Path::new(DEF_OUT_PATH), false);
+        let mut snapshots = HashMap::new();
+        snapshots.insert(path.to_path_buf(), TitleGrabber::read_lines(path));
+
+        // Line 2 changes, line 3 is a brand new line; line 1 is untouched.
+        fs::write(
+            path,
+            "https://a.example/one\nhttps://b.example/TWO-changed\nhttps://c.example/three\nhttps://d.example/four\n",
+        )
+        .unwrap();
+
+        let changed = instance.changed_urls(&mut snapshots, path);
+
+        assert_eq!(2, changed.len());
+        assert!(changed.contains("https://b.example/TWO-changed"));
+        assert!(changed.contains("https://d.example/four"));
+        assert!(!changed.contains("https://a.example/one"));
+        assert!(!changed.contains("https://c.example/three"));
+
+        // The snapshot is updated so a second, no-op diff reports nothing.
+        assert!(instance.changed_urls(&mut snapshots, path).is_empty());
+
+        assert!(fs::remove_file(path).is_ok());
+    }
+
+    #[test]
+    fn it_streams_a_run_with_no_urls_to_completion() {
+        env::set_var("TESTING", "1");
+        let out_path = Path::new("tests/fixtures/stream_empty_res.csv");
+        let instance = TitleGrabber::new(vec![], out_path, false);
+
+        let items: Vec<StreamItem> = instance.stream().unwrap().into_iter().collect();
+
+        // No URLs means no per-row messages; the background thread still
+        // finishes cleanly and drops the sender, ending the receiver's
+        // iterator instead of hanging.
+        assert!(items.is_empty());
+        assert!(out_path.exists());
+        assert!(fs::remove_file(out_path).is_ok());
+    }
+
     // #[test]
     // fn it_works() {
     //     env::set_var("TESTING", "1");
@@ -1,6 +1,7 @@
 use std::env;
 use std::fs;
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use csv;
 
@@ -112,3 +113,92 @@ fn it_works_with_twitter_status_update_urls() {
     assert!(iter.next().is_none());
     assert!(fs::remove_file(out_path).is_ok());
 }
+
+#[test]
+fn it_writes_through_fresh_cached_rows_to_a_distinct_cache_path() {
+    env::set_var("TESTING", "1");
+    let inputs_path = Path::new("tests/fixtures/cache_write_through_urls.txt");
+    let out_path = Path::new("tests/fixtures/cache_write_through_res.csv");
+    let cache_path = Path::new("tests/fixtures/cache_write_through_cache.csv");
+    let url = "https://unreachable.invalid/does-not-matter";
+    let cached_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    fs::write(inputs_path, format!("{}\n", url)).unwrap();
+    fs::write(
+        cache_path,
+        format!(
+            "url,end_url,page_title,article_title,etag,last_modified,content_type,cached_at\n{},{},Cached Title,,,,text/html,{}\n",
+            url, url, cached_at
+        ),
+    )
+    .unwrap();
+
+    let inputs = vec![inputs_path];
+    let mut instance = TitleGrabber::new(inputs, out_path, false);
+    instance.with_cache_path(cache_path);
+    // Well within cached_at's age, so this resolves from the cache alone -
+    // no network access needed for the test to be deterministic.
+    instance.with_max_cache_age(Duration::from_secs(3600));
+
+    assert!(instance.write_csv_file().is_ok());
+
+    // The resolved (here, cache-reused) row must have been written back to
+    // `cache_path`, not just `out_path` - otherwise `with_cache_path` is
+    // read-only and a second run can never reuse it.
+    let mut reader = csv::Reader::from_path(cache_path).unwrap();
+    let mut iter = reader.records();
+    let row = iter.next().expect("cache_path should contain 1 record").unwrap();
+    assert_eq!(Some(url), row.get(0));
+    assert_eq!(Some("Cached Title"), row.get(2));
+
+    fs::remove_file(inputs_path).ok();
+    fs::remove_file(out_path).ok();
+    fs::remove_file(cache_path).ok();
+}
+
+#[test]
+fn it_revalidates_from_the_default_output_file_across_runs() {
+    env::set_var("TESTING", "1");
+    let inputs_path = Path::new("tests/fixtures/revalidation_urls.txt");
+    let out_path = Path::new("tests/fixtures/revalidation_res.csv");
+    let url = "https://unreachable.invalid/revalidated-page";
+    let cached_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    fs::write(inputs_path, format!("{}\n", url)).unwrap();
+    // Seed `out_path` itself (the default cache location, with no explicit
+    // --cache-path) with a fresh cached row carrying validators.
+    fs::write(
+        out_path,
+        format!(
+            "url,end_url,page_title,article_title,etag,last_modified,content_type,cached_at\n{},{},Cached Title,,\"W/\\\"abc123\\\"\",\"Wed, 21 Oct 2015 07:28:00 GMT\",text/html,{}\n",
+            url, url, cached_at
+        ),
+    )
+    .unwrap();
+
+    let inputs = vec![inputs_path];
+    let mut instance = TitleGrabber::new(inputs, out_path, false);
+    // Well within cached_at's age, so the run below resolves entirely from
+    // `out_path`'s own prior content with no network access - regression
+    // coverage for processed_urls() having been read *before* write_to's
+    // caller truncated that same file.
+    instance.with_max_cache_age(Duration::from_secs(3600));
+
+    assert!(instance.write_csv_file().is_ok());
+
+    let mut reader = csv::Reader::from_path(out_path).unwrap();
+    let mut iter = reader.records();
+    let row = iter.next().expect("out_path should still have 1 record").unwrap();
+    assert_eq!(Some(url), row.get(0));
+    assert_eq!(Some("Cached Title"), row.get(2));
+    assert!(iter.next().is_none());
+
+    fs::remove_file(inputs_path).ok();
+    fs::remove_file(out_path).ok();
+}